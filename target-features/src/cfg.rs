@@ -0,0 +1,172 @@
+//! Parsing of `#[cfg(target_feature = "...")]`-style predicate expressions. Evaluation against a
+//! target is done by `Target::eval_cfg`.
+
+use std::fmt;
+
+/// A parsed `target_feature` cfg predicate expression.
+///
+/// Mirrors the `all(...)`/`any(...)`/`not(...)`/`target_feature = "..."` grammar used by
+/// `#[cfg(...)]` attributes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CfgExpr {
+    /// A single `target_feature = "name"` predicate.
+    TargetFeature(String),
+    /// `all(...)`: true if every sub-expression is true.
+    All(Vec<CfgExpr>),
+    /// `any(...)`: true if at least one sub-expression is true.
+    Any(Vec<CfgExpr>),
+    /// `not(...)`: true if the sub-expression is false.
+    Not(Box<CfgExpr>),
+}
+
+impl CfgExpr {
+    /// Parse a cfg expression such as
+    /// `all(target_feature = "avx2", any(target_feature = "fma", not(target_feature = "sse4.1")))`.
+    pub fn parse(input: &str) -> Result<Self, CfgParseError> {
+        let mut parser = Parser { input, pos: 0 };
+        let expr = parser.parse_expr()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.input.len() {
+            return Err(CfgParseError(format!(
+                "unexpected trailing input: {}",
+                &parser.input[parser.pos..]
+            )));
+        }
+        Ok(expr)
+    }
+}
+
+/// Returned by [`CfgExpr::parse`] when the input isn't a valid cfg expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CfgParseError(String);
+
+impl fmt::Display for CfgParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.input.len() - trimmed.len();
+    }
+
+    fn expect(&mut self, ch: char) -> Result<(), CfgParseError> {
+        if self.rest().starts_with(ch) {
+            self.pos += ch.len_utf8();
+            Ok(())
+        } else {
+            Err(CfgParseError(format!(
+                "expected `{ch}` at: {}",
+                self.rest()
+            )))
+        }
+    }
+
+    fn parse_ident(&mut self) -> Result<&'a str, CfgParseError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(CfgParseError(format!("expected an identifier at: {rest}")));
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn parse_string(&mut self) -> Result<String, CfgParseError> {
+        self.expect('"')?;
+        let rest = self.rest();
+        let len = rest
+            .find('"')
+            .ok_or_else(|| CfgParseError("unterminated string literal".to_string()))?;
+        let value = rest[..len].to_string();
+        self.pos += len;
+        self.expect('"')?;
+        Ok(value)
+    }
+
+    fn parse_paren_list(&mut self) -> Result<Vec<CfgExpr>, CfgParseError> {
+        self.expect('(')?;
+        let mut exprs = Vec::new();
+        loop {
+            self.skip_whitespace();
+            exprs.push(self.parse_expr()?);
+            self.skip_whitespace();
+            if self.rest().starts_with(',') {
+                self.pos += 1;
+                continue;
+            }
+            break;
+        }
+        self.skip_whitespace();
+        self.expect(')')?;
+        Ok(exprs)
+    }
+
+    fn parse_expr(&mut self) -> Result<CfgExpr, CfgParseError> {
+        self.skip_whitespace();
+        let ident = self.parse_ident()?;
+        self.skip_whitespace();
+
+        match ident {
+            "all" => Ok(CfgExpr::All(self.parse_paren_list()?)),
+            "any" => Ok(CfgExpr::Any(self.parse_paren_list()?)),
+            "not" => {
+                self.expect('(')?;
+                self.skip_whitespace();
+                let expr = self.parse_expr()?;
+                self.skip_whitespace();
+                self.expect(')')?;
+                Ok(CfgExpr::Not(Box::new(expr)))
+            }
+            "target_feature" => {
+                self.expect('=')?;
+                self.skip_whitespace();
+                let value = self.parse_string()?;
+                Ok(CfgExpr::TargetFeature(value))
+            }
+            other => Err(CfgParseError(format!("unknown cfg predicate `{other}`"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_all_any_not() {
+        let expr = CfgExpr::parse(
+            r#"all(target_feature = "avx2", any(target_feature = "fma", not(target_feature = "sse4.1")))"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            expr,
+            CfgExpr::All(vec![
+                CfgExpr::TargetFeature("avx2".to_string()),
+                CfgExpr::Any(vec![
+                    CfgExpr::TargetFeature("fma".to_string()),
+                    CfgExpr::Not(Box::new(CfgExpr::TargetFeature("sse4.1".to_string()))),
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_predicates() {
+        assert!(CfgExpr::parse(r#"bogus(target_feature = "avx2")"#).is_err());
+    }
+}
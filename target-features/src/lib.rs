@@ -5,6 +5,10 @@
 
 include!(concat!(env!("OUT_DIR"), "/generated.rs"));
 
+mod cfg;
+
+pub use cfg::{CfgExpr, CfgParseError};
+
 /// A target architecture.
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum Architecture {
@@ -31,10 +35,125 @@ pub enum Architecture {
 }
 
 /// Returned by [`Feature::new`] when the requested feature can't be found.
-pub struct UnknownFeature;
+#[derive(Debug)]
+pub struct UnknownFeature(Option<&'static str>);
+
+impl UnknownFeature {
+    /// Get the closest known feature name for the requested (but unknown) feature, if any came
+    /// close enough to suggest.
+    pub const fn suggestion(&self) -> Option<&'static str> {
+        self.0
+    }
+}
+
+/// Compute the Levenshtein edit distance between `a` and `b`.
+///
+/// Uses a rolling two-row buffer, bounded to strings of at most 63 bytes (well beyond any real
+/// target feature name), so the distance can be computed without allocating. Longer input yields
+/// `usize::MAX`, which never falls within a suggestion threshold.
+const fn levenshtein_distance(a: &str, b: &str) -> usize {
+    const MAX_LEN: usize = 64;
+
+    let a = a.as_bytes();
+    let b = b.as_bytes();
+
+    if a.len() >= MAX_LEN || b.len() >= MAX_LEN {
+        return usize::MAX;
+    }
+
+    let mut prev = [0usize; MAX_LEN];
+    let mut curr = [0usize; MAX_LEN];
+
+    let mut j = 0;
+    while j <= b.len() {
+        prev[j] = j;
+        j += 1;
+    }
+
+    let mut i = 1;
+    while i <= a.len() {
+        curr[0] = i;
+
+        let mut j = 1;
+        while j <= b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+
+            let deletion = prev[j] + 1;
+            let insertion = curr[j - 1] + 1;
+            let substitution = prev[j - 1] + cost;
+
+            let mut min = deletion;
+            if insertion < min {
+                min = insertion;
+            }
+            if substitution < min {
+                min = substitution;
+            }
+            curr[j] = min;
+            j += 1;
+        }
+
+        let mut j = 0;
+        while j <= b.len() {
+            prev[j] = curr[j];
+            j += 1;
+        }
+        i += 1;
+    }
+
+    prev[b.len()]
+}
+
+/// Returns whether the feature at index `from` implies the feature at index `target`, directly
+/// or transitively, walking the implication graph with a bounded worklist to stay const-friendly.
+const fn implies_transitively(from: usize, target: usize) -> bool {
+    let mut seen = [false; FEATURES.len()];
+    let mut worklist = [0usize; FEATURES.len()];
+    let mut len = 1;
+    worklist[0] = from;
+    seen[from] = true;
+
+    let mut i = 0;
+    while i < len {
+        let implies = Feature(worklist[i]).implies();
+        let mut j = 0;
+        while j < implies.len() {
+            let idx = implies[j].0;
+            if idx == target {
+                return true;
+            }
+            if !seen[idx] {
+                seen[idx] = true;
+                worklist[len] = idx;
+                len += 1;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+
+    false
+}
+
+/// The stability of a target feature on the Rust compiler.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum FeatureStability {
+    /// The feature is usable on stable Rust.
+    Stable {
+        /// The Rust version the feature was stabilized in.
+        since: &'static str,
+    },
+    /// The feature requires a nightly compiler and the given feature gate.
+    Unstable {
+        /// The name of the `#![feature(...)]` gate that unlocks this feature.
+        gate: &'static str,
+    },
+    /// The feature has been removed and is no longer recognized by rustc.
+    Removed,
+}
 
 /// A target feature.
-#[derive(PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct Feature(usize);
 
 impl Feature {
@@ -58,6 +177,21 @@ impl Feature {
             true
         }
 
+        // Lexical ordering, used to break distance ties the same way `Feature::suggest` does.
+        const fn str_lt(a: &str, b: &str) -> bool {
+            let a = a.as_bytes();
+            let b = b.as_bytes();
+
+            let mut i = 0;
+            while i < a.len() && i < b.len() {
+                if a[i] != b[i] {
+                    return a[i] < b[i];
+                }
+                i += 1;
+            }
+            a.len() < b.len()
+        }
+
         let mut i = 0;
         while i < FEATURES.len() {
             if (architecture as u8) == (FEATURES[i].0 as u8) && str_eq(feature, FEATURES[i].1) {
@@ -66,7 +200,58 @@ impl Feature {
             i += 1;
         }
 
-        Err(UnknownFeature)
+        // No exact match: look for the closest feature name to suggest instead.
+        let threshold = if feature.len() / 3 > 1 {
+            feature.len() / 3
+        } else {
+            1
+        };
+        let mut best: Option<&'static str> = None;
+        let mut best_distance = usize::MAX;
+        let mut i = 0;
+        while i < FEATURES.len() {
+            if (architecture as u8) == (FEATURES[i].0 as u8) {
+                let distance = levenshtein_distance(feature, FEATURES[i].1);
+                let better = match best {
+                    Some(current) => {
+                        distance < best_distance
+                            || (distance == best_distance && str_lt(FEATURES[i].1, current))
+                    }
+                    None => true,
+                };
+                if distance <= threshold && better {
+                    best_distance = distance;
+                    best = Some(FEATURES[i].1);
+                }
+            }
+            i += 1;
+        }
+
+        Err(UnknownFeature(best))
+    }
+
+    /// Suggest known feature names close to `name`, for the given architecture.
+    ///
+    /// Computes the Levenshtein edit distance between `name` and every feature name for
+    /// `architecture`, keeps the ones within `max(1, name.len() / 3)` edits, and returns them
+    /// sorted by ascending distance and then lexically. This matches how Cargo suggests unknown
+    /// feature names, and turns a mistyped name like `"avx-2"` or `"ssee4.1"` into a helpful hint.
+    pub fn suggest(architecture: Architecture, name: &str) -> Vec<&'static str> {
+        let threshold = if name.len() / 3 > 1 {
+            name.len() / 3
+        } else {
+            1
+        };
+
+        let mut matches: Vec<(usize, &'static str)> = FEATURES
+            .iter()
+            .filter(|feature| (architecture as u8) == (feature.0 as u8))
+            .map(|feature| (levenshtein_distance(name, feature.1), feature.1))
+            .filter(|(distance, _)| *distance <= threshold)
+            .collect();
+
+        matches.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(b.1)));
+        matches.into_iter().map(|(_, name)| name).collect()
     }
 
     /// Get the name of the feature.
@@ -90,6 +275,124 @@ impl Feature {
     pub const fn implies(&self) -> &'static [Feature] {
         FEATURES[self.0].3
     }
+
+    /// Return every feature transitively implied by the existence of this feature.
+    ///
+    /// Unlike [`implies`](Self::implies), which only returns the directly implied features, this
+    /// walks the implication graph to a fixed point. For example, "avx2" implies "avx", which in
+    /// turn implies "sse4.2", and this returns the full transitive set.
+    pub fn implied_features(&self) -> Vec<Feature> {
+        let mut seen = vec![false; FEATURES.len()];
+        let mut worklist = vec![self.0];
+        seen[self.0] = true;
+
+        let mut i = 0;
+        while i < worklist.len() {
+            for implied in Feature(worklist[i]).implies() {
+                if !seen[implied.0] {
+                    seen[implied.0] = true;
+                    worklist.push(implied.0);
+                }
+            }
+            i += 1;
+        }
+
+        worklist
+            .into_iter()
+            .filter(|&idx| idx != self.0)
+            .map(Feature)
+            .collect()
+    }
+
+    /// Return the features that must be enabled or disabled together with this one.
+    ///
+    /// For example, aarch64's `paca` and `pacg`.
+    pub const fn tied_with(&self) -> &'static [Feature] {
+        FEATURES[self.0].5
+    }
+
+    /// Get the stability of this feature on the Rust compiler.
+    pub const fn stability(&self) -> FeatureStability {
+        FEATURES[self.0].4
+    }
+
+    /// Returns whether this feature can be enabled on stable Rust.
+    pub const fn is_stable(&self) -> bool {
+        matches!(self.stability(), FeatureStability::Stable { .. })
+    }
+
+    /// Returns the `#![feature(...)]` gate required to enable this feature on nightly Rust.
+    ///
+    /// Returns `None` if the feature is already stable or has been removed.
+    pub const fn feature_gate(&self) -> Option<&'static str> {
+        match self.stability() {
+            FeatureStability::Unstable { gate } => Some(gate),
+            FeatureStability::Stable { .. } | FeatureStability::Removed => None,
+        }
+    }
+}
+
+/// Returned by [`Cpu::new`] when the requested CPU can't be found.
+#[derive(Debug)]
+pub struct UnknownCpu;
+
+/// Returned by [`Target::validate`] when a tied feature group is only partially enabled.
+#[derive(Debug)]
+pub struct TiedFeatureError {
+    /// One of the features found in the inconsistent tied group.
+    pub feature: Feature,
+}
+
+/// A `-Ctarget-cpu` CPU preset, expanding into the set of features that CPU implies.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Cpu(usize);
+
+impl Cpu {
+    /// Look up a CPU preset.
+    pub const fn new(architecture: Architecture, name: &str) -> Result<Self, UnknownCpu> {
+        const fn str_eq(a: &str, b: &str) -> bool {
+            let a = a.as_bytes();
+            let b = b.as_bytes();
+
+            if a.len() != b.len() {
+                return false;
+            }
+
+            let mut i = 0;
+            while i < a.len() {
+                if a[i] != b[i] {
+                    return false;
+                }
+                i += 1;
+            }
+            true
+        }
+
+        let mut i = 0;
+        while i < CPUS.len() {
+            if (architecture as u8) == (CPUS[i].0 as u8) && str_eq(name, CPUS[i].1) {
+                return Ok(Self(i));
+            }
+            i += 1;
+        }
+
+        Err(UnknownCpu)
+    }
+
+    /// Get the name of this CPU preset.
+    pub const fn name(&self) -> &'static str {
+        CPUS[self.0].1
+    }
+
+    /// Get the architecture this CPU preset is for.
+    pub const fn architecture(&self) -> Architecture {
+        CPUS[self.0].0
+    }
+
+    /// Return all features this CPU preset enables.
+    pub const fn features(&self) -> &'static [Feature] {
+        CPUS[self.0].2
+    }
 }
 
 /// A target architecture with optional features.
@@ -127,7 +430,7 @@ impl Target {
                 let implies = Feature(i).implies();
                 let mut j = 0;
                 while j < implies.len() {
-                    if feature.0 == implies[0].0 {
+                    if feature.0 == implies[j].0 {
                         return true;
                     }
                     j += 1;
@@ -152,13 +455,38 @@ impl Target {
         }
     }
 
-    /// Add a feature to the target.
+    /// Add a feature to the target, transitively enabling everything it implies.
+    ///
+    /// For example, enabling "avx2" also enables "avx" and "sse".
     ///
     /// # Panics
     /// Panics if the feature doesn't belong to the target architecture.
     pub const fn with_feature(mut self, feature: Feature) -> Self {
         assert!(feature.architecture() as u8 == self.architecture as u8);
         self.features[feature.0] = true;
+
+        // Walk the implication graph to a fixed point, using the feature array itself as the
+        // "seen" set and a bounded worklist to stay allocation-free.
+        let mut worklist = [0usize; FEATURES.len()];
+        let mut len = 1;
+        worklist[0] = feature.0;
+
+        let mut i = 0;
+        while i < len {
+            let implies = Feature(worklist[i]).implies();
+            let mut j = 0;
+            while j < implies.len() {
+                let idx = implies[j].0;
+                if !self.features[idx] {
+                    self.features[idx] = true;
+                    worklist[len] = idx;
+                    len += 1;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+
         self
     }
 
@@ -174,13 +502,32 @@ impl Target {
         }
     }
 
-    /// Remove a feature from the target.
+    /// Remove a feature from the target, cascading to any features tied to it or that imply it.
+    ///
+    /// A still-enabled feature that (directly or transitively) implies the removed one would
+    /// otherwise keep it in effect, so every such feature is disabled too.
     ///
     /// # Panics
     /// Panics if the feature doesn't belong to the target architecture.
     pub const fn without_feature(mut self, feature: Feature) -> Self {
         assert!(feature.architecture() as u8 == self.architecture as u8);
         self.features[feature.0] = false;
+
+        let mut i = 0;
+        while i < self.features.len() {
+            if self.features[i] && implies_transitively(i, feature.0) {
+                self.features[i] = false;
+            }
+            i += 1;
+        }
+
+        let tied = feature.tied_with();
+        let mut i = 0;
+        while i < tied.len() {
+            self.features[tied[i].0] = false;
+            i += 1;
+        }
+
         self
     }
 
@@ -195,4 +542,282 @@ impl Target {
             panic!("unknown feature");
         }
     }
-}
\ No newline at end of file
+
+    /// Add the features of a `-Ctarget-cpu` CPU preset to the target, enabling every feature it
+    /// implies. Features already enabled on the target stay enabled.
+    ///
+    /// # Panics
+    /// Panics if the CPU preset doesn't belong to the target architecture.
+    pub const fn with_cpu(mut self, cpu: Cpu) -> Self {
+        assert!(cpu.architecture() as u8 == self.architecture as u8);
+
+        let enabled = cpu.features();
+        let mut i = 0;
+        while i < enabled.len() {
+            self = self.with_feature(Feature(enabled[i].0));
+            i += 1;
+        }
+        self
+    }
+
+    /// Check that every tied feature group on this target is either fully enabled or fully
+    /// disabled.
+    ///
+    /// # Errors
+    /// Returns an error identifying a feature in the first group found where only part of the
+    /// group is enabled.
+    pub const fn validate(&self) -> Result<(), TiedFeatureError> {
+        let mut i = 0;
+        while i < self.features.len() {
+            if self.features[i] {
+                let tied = Feature(i).tied_with();
+                let mut j = 0;
+                while j < tied.len() {
+                    if !self.features[tied[j].0] {
+                        return Err(TiedFeatureError {
+                            feature: Feature(i),
+                        });
+                    }
+                    j += 1;
+                }
+            }
+            i += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Apply a `+feat,-feat` target-feature string, such as the one passed to
+    /// `RUSTFLAGS=-Ctarget-feature=...`, to this target.
+    ///
+    /// Each comma-separated token must start with `+` (enable) or `-` (disable) followed by a
+    /// feature name; malformed tokens are skipped. `on_unknown` controls what happens when a
+    /// token names a feature that doesn't exist for this target's architecture.
+    pub fn apply_feature_str(
+        &mut self,
+        features: &str,
+        on_unknown: UnknownFeatureBehavior,
+    ) -> Result<(), UnknownFeature> {
+        for token in features.split(',') {
+            let token = token.trim();
+            if token.is_empty() {
+                continue;
+            }
+
+            let (enable, name) = if let Some(name) = token.strip_prefix('+') {
+                (true, name)
+            } else if let Some(name) = token.strip_prefix('-') {
+                (false, name)
+            } else {
+                continue;
+            };
+
+            match Feature::new(self.architecture, name) {
+                Ok(feature) => {
+                    *self = if enable {
+                        self.with_feature(feature)
+                    } else {
+                        self.without_feature(feature)
+                    };
+                }
+                Err(err) => match on_unknown {
+                    UnknownFeatureBehavior::Ignore => {}
+                    UnknownFeatureBehavior::Error => return Err(err),
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Serialize the enabled features of this target into `+feat,...` form, as accepted by
+    /// `RUSTFLAGS=-Ctarget-feature=...`.
+    pub fn to_feature_string(&self) -> String {
+        FEATURES
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.features[*i])
+            .map(|(_, feature)| format!("+{}", feature.1))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Build a target from the `CARGO_CFG_TARGET_FEATURE` environment variable, which Cargo sets
+    /// to the exact feature set the current compilation was invoked with. Build scripts and
+    /// proc-macros can use this to learn which SIMD paths are available without re-deriving them.
+    pub fn from_cargo_cfg(architecture: Architecture) -> Self {
+        let mut target = Self::new(architecture);
+
+        if let Ok(features) = std::env::var("CARGO_CFG_TARGET_FEATURE") {
+            for name in features.split(',') {
+                if let Ok(feature) = Feature::new(architecture, name) {
+                    target = target.with_feature(feature);
+                }
+            }
+        }
+
+        target
+    }
+
+    /// Evaluate a [`CfgExpr`] against this target.
+    ///
+    /// `target_feature = "name"` leaves are resolved through [`supports_feature`](Self::supports_feature),
+    /// with unknown names evaluating to `false` rather than panicking.
+    pub fn eval_cfg(&self, expr: &CfgExpr) -> bool {
+        match expr {
+            CfgExpr::TargetFeature(name) => Feature::new(self.architecture, name)
+                .map(|feature| self.supports_feature(feature))
+                .unwrap_or(false),
+            CfgExpr::All(exprs) => exprs.iter().all(|expr| self.eval_cfg(expr)),
+            CfgExpr::Any(exprs) => exprs.iter().any(|expr| self.eval_cfg(expr)),
+            CfgExpr::Not(expr) => !self.eval_cfg(expr),
+        }
+    }
+}
+
+/// Controls how [`Target::apply_feature_str`] handles a token naming an unknown feature.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum UnknownFeatureBehavior {
+    /// Silently skip tokens naming unknown features.
+    Ignore,
+    /// Return an error for the first unknown feature token encountered.
+    Error,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggest_finds_near_miss_feature_names() {
+        let suggestions = Feature::suggest(Architecture::X86, "avx-2");
+        assert!(suggestions.contains(&"avx2"));
+    }
+
+    #[test]
+    fn unknown_feature_carries_the_best_suggestion() {
+        let err = Feature::new(Architecture::X86, "avx-2").unwrap_err();
+        assert_eq!(err.suggestion(), Some("avx2"));
+    }
+
+    #[test]
+    fn apply_feature_str_enables_and_disables_features() {
+        // "avx2" and "fma" don't imply one another, so disabling one can't cascade onto the
+        // other the way two features in the same implication chain would.
+        let mut target = Target::new(Architecture::X86).with_feature_str("fma");
+        target
+            .apply_feature_str("+avx2,-fma", UnknownFeatureBehavior::Ignore)
+            .unwrap();
+        assert!(target.supports_feature_str("avx2"));
+        assert!(!target.supports_feature_str("fma"));
+    }
+
+    #[test]
+    fn apply_feature_str_ignores_unknown_tokens_when_asked() {
+        let mut target = Target::new(Architecture::X86);
+        assert!(target
+            .apply_feature_str("+not-a-real-feature", UnknownFeatureBehavior::Ignore)
+            .is_ok());
+    }
+
+    #[test]
+    fn apply_feature_str_errors_on_unknown_tokens_when_asked() {
+        let mut target = Target::new(Architecture::X86);
+        assert!(target
+            .apply_feature_str("+not-a-real-feature", UnknownFeatureBehavior::Error)
+            .is_err());
+    }
+
+    #[test]
+    fn apply_feature_str_skips_malformed_tokens_without_panicking() {
+        let mut target = Target::new(Architecture::X86);
+        assert!(target
+            .apply_feature_str("éavx2", UnknownFeatureBehavior::Error)
+            .is_ok());
+        assert!(!target.supports_feature_str("avx2"));
+    }
+
+    #[test]
+    fn eval_cfg_evaluates_nested_expressions() {
+        let target = Target::new(Architecture::X86)
+            .with_feature_str("avx2")
+            .with_feature_str("fma");
+        let expr = CfgExpr::parse(
+            r#"all(target_feature = "avx2", any(target_feature = "fma", not(target_feature = "sse4.1")))"#,
+        )
+        .unwrap();
+        assert!(target.eval_cfg(&expr));
+    }
+
+    #[test]
+    fn eval_cfg_treats_unknown_features_as_false() {
+        let target = Target::new(Architecture::X86);
+        let expr = CfgExpr::parse(r#"target_feature = "not-a-real-feature""#).unwrap();
+        assert!(!target.eval_cfg(&expr));
+    }
+
+    #[test]
+    fn stable_feature_reports_stability_and_no_gate() {
+        let feature = Feature::new(Architecture::X86, "sse2").unwrap();
+        assert!(feature.is_stable());
+        assert_eq!(feature.feature_gate(), None);
+    }
+
+    #[test]
+    fn unstable_feature_reports_its_gate() {
+        let feature = Feature::new(Architecture::X86, "avx512f").unwrap();
+        assert!(!feature.is_stable());
+        assert_eq!(feature.feature_gate(), Some("avx512_target_feature"));
+    }
+
+    #[test]
+    fn cpu_preset_expands_into_its_features() {
+        let cpu = Cpu::new(Architecture::X86, "x86-64-v3").unwrap();
+        let target = Target::new(Architecture::X86).with_cpu(cpu);
+        assert!(target.supports_feature_str("avx2"));
+    }
+
+    #[test]
+    fn unknown_cpu_preset_is_rejected() {
+        assert!(Cpu::new(Architecture::X86, "not-a-real-cpu").is_err());
+    }
+
+    #[test]
+    fn implied_features_returns_the_full_transitive_closure() {
+        let avx2 = Feature::new(Architecture::X86, "avx2").unwrap();
+        let names: Vec<_> = avx2.implied_features().iter().map(Feature::name).collect();
+        assert!(names.contains(&"avx"));
+        assert!(names.contains(&"sse4.1"));
+    }
+
+    #[test]
+    fn tied_with_reports_the_paired_feature() {
+        let paca = Feature::new(Architecture::AArch64, "paca").unwrap();
+        let names: Vec<_> = paca.tied_with().iter().map(Feature::name).collect();
+        assert!(names.contains(&"pacg"));
+    }
+
+    #[test]
+    fn validate_detects_a_partially_enabled_tied_group() {
+        let target = Target::new(Architecture::AArch64).with_feature_str("paca");
+        assert!(target.validate().is_err());
+    }
+
+    #[test]
+    fn validate_accepts_a_fully_enabled_tied_group() {
+        let target = Target::new(Architecture::AArch64)
+            .with_feature_str("paca")
+            .with_feature_str("pacg");
+        assert!(target.validate().is_ok());
+    }
+
+    #[test]
+    fn without_feature_cascades_to_everything_that_implies_it() {
+        let target = Target::new(Architecture::X86).with_feature_str("avx2");
+        assert!(target.supports_feature_str("sse4.1"));
+
+        let target = target.without_feature_str("sse4.1");
+        assert!(!target.supports_feature_str("sse4.1"));
+        assert!(!target.supports_feature_str("avx2"));
+    }
+}